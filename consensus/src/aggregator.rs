@@ -1,20 +1,351 @@
+use crate::batch_verify::MultiThreadedBatchVerifier;
 use crate::config::{Committee, Stake};
 use crate::core::{SeqNumber, OPT, PES, PRE_ONE, PRE_TWO, RBC_ECHO, RBC_READY};
 use crate::error::{ConsensusError, ConsensusResult};
-use crate::messages::{EchoVote, Prepare, RBCProof, RandomnessShare, ReadyVote};
-use crypto::{PublicKey, Signature};
-use std::collections::{BTreeMap, HashMap, HashSet};
+use crate::messages::{Prepare, RandomnessShare, ReadyVote};
+use crypto::{Digest, PublicKey, Signature};
+use std::collections::{BTreeMap, HashMap};
 use std::convert::TryInto;
+use std::sync::mpsc as std_mpsc;
+use std::thread;
 use threshold_crypto::PublicKeySet;
+use tokio::sync::mpsc::Sender;
 
 #[cfg(test)]
 #[path = "tests/aggregator_tests.rs"]
 pub mod aggregator_tests;
 
+/// Per-vote-type tag mixed into `vote_digest` so a signature collected for
+/// one role/slot can never be replayed as a vote for another (an
+/// `RBC_ECHO` signature standing in for `RBC_READY`/`PRE_ONE`/`PRE_TWO`/a
+/// coin share at the same height, say).
+#[derive(Clone, Copy)]
+enum VoteDomain {
+    RbcEcho,
+    RbcReady,
+    PrepareOne,
+    PrepareTwo,
+    CoinShare,
+}
+
+impl VoteDomain {
+    fn tag(self) -> u8 {
+        match self {
+            VoteDomain::RbcEcho => b'e',
+            VoteDomain::RbcReady => b'r',
+            VoteDomain::PrepareOne => b'1',
+            VoteDomain::PrepareTwo => b'2',
+            VoteDomain::CoinShare => b's',
+        }
+    }
+}
+
+/// Digest that `signature` is checked against:
+/// `H(domain_tag || epoch || height || phase || value)`, where `value` is
+/// whatever the vote actually attests to (the RBC block digest for
+/// echo/ready votes, the `OPT`/`PES` byte for prepare votes, the round
+/// number for coin shares). Mixing in the domain tag plus epoch/height/
+/// phase is what stops a signature collected for one slot/role from
+/// verifying as a vote for another -- the same construction `aba_conf_digest`
+/// and `val_shard_digest` in `core.rs` already use.
+///
+/// This is the correct design and is NOT optional: hashing `value` alone
+/// is catastrophic for `Prepare` votes in particular, since `value` there
+/// is the single `OPT`/`PES` byte (aggregator.rs, `PrepareMaker::append`)
+/// -- a bare `H(value)` digest has only two possible outputs for every
+/// prepare vote any validator will ever cast, at any epoch, height, or
+/// phase, for the lifetime of the chain. That is not a narrower "replay
+/// across tags" bug, it is no domain separation at all.
+///
+/// REQUEST chunk0-4 IS NOT DONE: landing this digest here requires a
+/// matching signing-side change -- `ReadyVote::new`/`Prepare::new`/
+/// coin-share signing must sign over this exact composite digest instead
+/// of the bare value -- in `messages.rs`, which is not part of this
+/// checkout and cannot be touched from here. Until that companion change
+/// lands, `verify_vote_signature` below will reject every vote signed the
+/// old way. That is the correct failure mode: rejecting legitimate votes
+/// is recoverable once the signing side catches up, where silently
+/// accepting signatures over an unbound digest is not. Do not paper over
+/// this by reverting to `Digest::hash(value)` again -- that "fix" is what
+/// created the Prepare-vote collision described above. This request stays
+/// open until the signing-side change ships alongside it.
+fn vote_digest(domain: VoteDomain, epoch: SeqNumber, height: SeqNumber, phase: u8, value: &[u8]) -> Digest {
+    let mut bytes = Vec::with_capacity(1 + 8 + 8 + 1 + value.len());
+    bytes.push(domain.tag());
+    bytes.extend_from_slice(&epoch.to_le_bytes());
+    bytes.extend_from_slice(&height.to_le_bytes());
+    bytes.push(phase);
+    bytes.extend_from_slice(value);
+    Digest::hash(&bytes)
+}
+
+/// Verify that `signature` over the domain-separated digest for this slot
+/// is valid under `author`'s public key -- see `vote_digest`.
+fn verify_vote_signature(
+    author: &PublicKey,
+    signature: &Signature,
+    domain: VoteDomain,
+    epoch: SeqNumber,
+    height: SeqNumber,
+    phase: u8,
+    value: &[u8],
+) -> ConsensusResult<()> {
+    let digest = vote_digest(domain, epoch, height, phase, value);
+    signature
+        .verify(&digest, author)
+        .map_err(|_| ConsensusError::InvalidVoteSignature(*author))
+}
+
+/// Result of combining a round's worth of coin shares, delivered back to the
+/// core's event loop over the channel it already `select!`s on.
+#[derive(Clone, Debug)]
+pub struct CoinResult {
+    pub epoch: SeqNumber,
+    pub height: SeqNumber,
+    pub round: SeqNumber,
+    pub coin: usize,
+}
+
+struct CoinJob {
+    epoch: SeqNumber,
+    height: SeqNumber,
+    round: SeqNumber,
+    shares: Vec<RandomnessShare>,
+    pk_set: PublicKeySet,
+    /// The committee current at dispatch time, carried per-job rather than
+    /// captured once at `CoinWorker::spawn` -- a reconfiguration landing
+    /// between two rounds must not leave this worker mapping authors to ids
+    /// under a stale committee.
+    committee: Committee,
+}
+
+/// Runs threshold-signature combination (Lagrange interpolation + pairing)
+/// on a dedicated thread so a hundred-microsecond-to-millisecond crypto job
+/// never stalls the core's message-processing loop.
+struct CoinWorker {
+    tx_job: std_mpsc::Sender<CoinJob>,
+}
+
+impl CoinWorker {
+    fn spawn(tx_result: Sender<CoinResult>) -> Self {
+        let (tx_job, rx_job) = std_mpsc::channel::<CoinJob>();
+        thread::spawn(move || {
+            while let Ok(job) = rx_job.recv() {
+                let mut sigs = BTreeMap::new();
+                for share in &job.shares {
+                    sigs.insert(job.committee.id(share.author), share.signature_share.clone());
+                }
+                if let Ok(sig) = job.pk_set.combine_signatures(sigs.iter()) {
+                    let coin =
+                        usize::from_be_bytes((&sig.to_bytes()[0..8]).try_into().unwrap()) % 2;
+                    let result = CoinResult {
+                        epoch: job.epoch,
+                        height: job.height,
+                        round: job.round,
+                        coin,
+                    };
+                    // The core is still alive for as long as the aggregator (and
+                    // hence this worker) is; a closed channel just means shutdown.
+                    let _ = tx_result.blocking_send(result);
+                }
+            }
+        });
+        Self { tx_job }
+    }
+
+    fn dispatch(&self, job: CoinJob) {
+        // The receiving thread only exits when the channel is dropped.
+        let _ = self.tx_job.send(job);
+    }
+}
+
+/// A full signed message kept around by a vote collector so that a second,
+/// conflicting contribution from the same author can be turned into proof
+/// of equivocation rather than just an `AuthorityReuse` error.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SignedVote {
+    /// Echo of an erasure-coded val's Merkle root. Carries the same
+    /// `ReadyVote` shape as a ready vote -- only the domain tag passed to
+    /// `RBCProofMaker::append` tells the two apart.
+    Echo(ReadyVote),
+    Ready(ReadyVote),
+    Prepare(Prepare),
+    Coin(RandomnessShare),
+}
+
+/// Cryptographic evidence that `author` contributed two different votes for
+/// the same `(epoch, height, tag[, phase])` slot. Both enclosed votes carry
+/// their own valid signature by `author`; verifying a proof only requires
+/// checking those two signatures and that the votes differ while addressing
+/// the same slot (for RBC and prepare votes, different signed values; for
+/// coin shares, two distinct shares both valid over that slot's one digest).
+#[derive(Clone, Debug)]
+pub struct EquivocationProof {
+    pub author: PublicKey,
+    pub first: SignedVote,
+    pub second: SignedVote,
+}
+
+impl EquivocationProof {
+    /// Verify that both votes are validly signed by `author` and that they
+    /// genuinely disagree (a different signed value for the same slot, or
+    /// for coin shares two distinct shares for the same round), as opposed
+    /// to being two copies of the same vote. `pk_set` is only needed to
+    /// check a `Coin` pair and may be omitted (as `None`) from call sites
+    /// that can't produce one -- any proof enclosing coin shares is then
+    /// rejected rather than silently passed.
+    pub fn verify(&self, committee: &Committee, pk_set: Option<&PublicKeySet>) -> ConsensusResult<()> {
+        ensure!(
+            self.first != self.second,
+            ConsensusError::InvalidEquivocationProof(self.author)
+        );
+        match (&self.first, &self.second) {
+            (SignedVote::Echo(a), SignedVote::Echo(b)) => {
+                a.verify(committee)?;
+                b.verify(committee)?;
+                ensure!(
+                    a.author == self.author
+                        && b.author == self.author
+                        && a.epoch == b.epoch
+                        && a.height == b.height
+                        && a.digest != b.digest,
+                    ConsensusError::InvalidEquivocationProof(self.author)
+                );
+            }
+            (SignedVote::Ready(a), SignedVote::Ready(b)) => {
+                a.verify(committee)?;
+                b.verify(committee)?;
+                ensure!(
+                    a.author == self.author
+                        && b.author == self.author
+                        && a.epoch == b.epoch
+                        && a.height == b.height
+                        && a.digest != b.digest,
+                    ConsensusError::InvalidEquivocationProof(self.author)
+                );
+            }
+            (SignedVote::Prepare(a), SignedVote::Prepare(b)) => {
+                a.verify(committee)?;
+                b.verify(committee)?;
+                ensure!(
+                    a.author == self.author
+                        && b.author == self.author
+                        && a.epoch == b.epoch
+                        && a.height == b.height
+                        && a.phase == b.phase
+                        && a.val != b.val,
+                    ConsensusError::InvalidEquivocationProof(self.author)
+                );
+            }
+            (SignedVote::Coin(a), SignedVote::Coin(b)) => {
+                let pk_set = pk_set.ok_or(ConsensusError::InvalidEquivocationProof(self.author))?;
+                ensure!(
+                    a.epoch == b.epoch && a.height == b.height && a.round == b.round,
+                    ConsensusError::InvalidEquivocationProof(self.author)
+                );
+                let id = committee.id(self.author);
+                let digest = vote_digest(VoteDomain::CoinShare, a.epoch, a.height, 0, &a.round.to_le_bytes());
+                ensure!(
+                    pk_set
+                        .public_key_share(id)
+                        .verify(&a.signature_share, digest.to_vec())
+                        && pk_set
+                            .public_key_share(id)
+                            .verify(&b.signature_share, digest.to_vec()),
+                    ConsensusError::InvalidEquivocationProof(self.author)
+                );
+            }
+            _ => return Err(ConsensusError::InvalidEquivocationProof(self.author)),
+        }
+        Ok(())
+    }
+}
+
+/// Outcome of feeding a vote into a quorum maker: either nothing yet, a
+/// freshly closed quorum, proof that the author double-voted, or the vote
+/// fell outside the aggregator's live `[last_committed, last_committed +
+/// horizon]` window and was never accumulated.
+pub enum AppendOutcome<T> {
+    Pending,
+    Quorum(T),
+    Equivocation(Box<EquivocationProof>),
+    TooOld,
+    TooFarAhead,
+}
+
+/// Identifies one in-flight quorum instance for the progress-query API,
+/// i.e. the same key a maker is stored under in the `Aggregator`'s maps.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum VoteKind {
+    Echo(SeqNumber, SeqNumber),
+    Ready(SeqNumber, SeqNumber),
+    Prepare(SeqNumber, SeqNumber, u8),
+    Coin(SeqNumber, SeqNumber, SeqNumber),
+}
+
+/// Generic "dedupe by author, accumulate stake, fire when a quorum
+/// predicate over `Tally` is reached" core shared by RBC vote, prepare
+/// vote, and coin-share aggregation. `Tally` carries whatever extra
+/// per-instantiation state a given vote type needs folded in alongside the
+/// running stake total: `()` for RBC votes and coin shares, `(opt, pes)`
+/// stake pairs for prepare votes.
+struct VoteCollector<Tally> {
+    tally: Tally,
+    weight: Stake,
+    contributors: Vec<PublicKey>,
+}
+
+impl<Tally: Default> VoteCollector<Tally> {
+    fn new() -> Self {
+        Self {
+            tally: Tally::default(),
+            weight: 0,
+            contributors: Vec::new(),
+        }
+    }
+
+    /// Record a first-time contribution from `author`, folding it into
+    /// `Tally` via `fold`. Callers are responsible for rejecting/flagging a
+    /// second contribution from the same author before calling this.
+    fn record(&mut self, author: PublicKey, stake: Stake, fold: impl FnOnce(&mut Tally)) {
+        self.contributors.push(author);
+        self.weight += stake;
+        fold(&mut self.tally);
+    }
+
+    fn weight(&self) -> Stake {
+        self.weight
+    }
+
+    fn tally(&self) -> &Tally {
+        &self.tally
+    }
+
+    /// Remaining stake needed to reach `quorum`, or 0 if already there.
+    fn weight_toward(&self, quorum: Stake) -> Stake {
+        quorum.saturating_sub(self.weight)
+    }
+
+    /// Progress towards `quorum` as a fraction in `[0, 1]`.
+    fn fraction_to_quorum(&self, quorum: Stake) -> f64 {
+        if quorum == 0 {
+            1.0
+        } else {
+            (self.weight as f64 / quorum as f64).min(1.0)
+        }
+    }
+
+    fn contributors(&self) -> &[PublicKey] {
+        &self.contributors
+    }
+}
+
 // In HotStuff, votes/timeouts aggregated by round
 // In VABA and async fallback, votes aggregated by round, timeouts/coin_share aggregated by view
 pub struct Aggregator {
-    committee: Committee,
+    coin_worker: CoinWorker,
+    horizon: SeqNumber,
+    last_committed_rank: SeqNumber,
     share_coin_aggregators: HashMap<(SeqNumber, SeqNumber, SeqNumber), Box<RandomCoinMaker>>,
     echo_vote_aggregators: HashMap<(SeqNumber, SeqNumber), Box<RBCProofMaker>>,
     ready_vote_aggregators: HashMap<(SeqNumber, SeqNumber), Box<RBCProofMaker>>,
@@ -22,9 +353,23 @@ pub struct Aggregator {
 }
 
 impl Aggregator {
-    pub fn new(committee: Committee) -> Self {
+    /// `tx_coin_result` is the channel end the core's `run` loop already
+    /// `select!`s on for coin-combination results from the crypto worker.
+    /// `horizon` bounds how far past `last_committed` an incoming vote's
+    /// rank may be before it's rejected without allocating a maker.
+    ///
+    /// Unlike earlier revisions, this no longer caches a `Committee`: an
+    /// in-flight vote's `epoch` can lag the core's current epoch across a
+    /// reconfiguration, so every call below takes the target epoch's
+    /// `Committee` explicitly from the caller (which already resolves it via
+    /// `Core::committee_for(epoch)`) instead of resolving stake/thresholds
+    /// against whatever committee happens to be "current" right now.
+    pub fn new(tx_coin_result: Sender<CoinResult>, horizon: SeqNumber) -> Self {
+        let coin_worker = CoinWorker::spawn(tx_coin_result);
         Self {
-            committee,
+            coin_worker,
+            horizon,
+            last_committed_rank: 0,
             share_coin_aggregators: HashMap::new(),
             echo_vote_aggregators: HashMap::new(),
             ready_vote_aggregators: HashMap::new(),
@@ -32,7 +377,47 @@ impl Aggregator {
         }
     }
 
-    pub fn add_rbc_echo_vote(&mut self, vote: EchoVote) -> ConsensusResult<Option<RBCProof>> {
+    fn rank(&self, epoch: SeqNumber, height: SeqNumber, committee: &Committee) -> SeqNumber {
+        epoch * (committee.size() as SeqNumber) + height
+    }
+
+    /// Classify `(epoch, height)` against the live window
+    /// `[last_committed_rank, last_committed_rank + horizon]`.
+    fn window_check<T>(
+        &self,
+        epoch: SeqNumber,
+        height: SeqNumber,
+        committee: &Committee,
+    ) -> Option<AppendOutcome<T>> {
+        let rank = self.rank(epoch, height, committee);
+        if rank < self.last_committed_rank {
+            Some(AppendOutcome::TooOld)
+        } else if rank > self.last_committed_rank + self.horizon {
+            Some(AppendOutcome::TooFarAhead)
+        } else {
+            None
+        }
+    }
+
+    /// Called by the core when it commits `(epoch, height)`, advancing the
+    /// live window so stale or far-future votes can be rejected before an
+    /// aggregator entry is ever allocated for them. `committee` is the
+    /// committee governing `epoch`, i.e. `Core::committee_for(epoch)`.
+    pub fn advance_committed(&mut self, epoch: SeqNumber, height: SeqNumber, committee: &Committee) {
+        let rank = self.rank(epoch, height, committee);
+        if rank > self.last_committed_rank {
+            self.last_committed_rank = rank;
+        }
+    }
+
+    pub fn add_rbc_echo_vote(
+        &mut self,
+        vote: ReadyVote,
+        committee: &Committee,
+    ) -> ConsensusResult<AppendOutcome<CompactProof>> {
+        if let Some(outcome) = self.window_check(vote.epoch, vote.height, committee) {
+            return Ok(outcome);
+        }
         self.echo_vote_aggregators
             .entry((vote.epoch, vote.height))
             .or_insert_with(|| Box::new(RBCProofMaker::new()))
@@ -41,12 +426,20 @@ impl Aggregator {
                 vote.height,
                 vote.author,
                 RBC_ECHO,
-                vote.signature,
-                &self.committee,
+                vote.signature.clone(),
+                SignedVote::Echo(vote),
+                committee,
             )
     }
 
-    pub fn add_rbc_ready_vote(&mut self, vote: ReadyVote) -> ConsensusResult<Option<RBCProof>> {
+    pub fn add_rbc_ready_vote(
+        &mut self,
+        vote: ReadyVote,
+        committee: &Committee,
+    ) -> ConsensusResult<AppendOutcome<CompactProof>> {
+        if let Some(outcome) = self.window_check(vote.epoch, vote.height, committee) {
+            return Ok(outcome);
+        }
         self.ready_vote_aggregators
             .entry((vote.epoch, vote.height))
             .or_insert_with(|| Box::new(RBCProofMaker::new()))
@@ -55,31 +448,47 @@ impl Aggregator {
                 vote.height,
                 vote.author,
                 RBC_READY,
-                vote.signature,
-                &self.committee,
+                vote.signature.clone(),
+                SignedVote::Ready(vote),
+                committee,
             )
     }
 
-    pub fn add_prepare_vote(&mut self, prepare: Prepare) -> ConsensusResult<Option<(u8, bool)>> {
+    pub fn add_prepare_vote(
+        &mut self,
+        prepare: Prepare,
+        committee: &Committee,
+    ) -> ConsensusResult<AppendOutcome<(u8, bool)>> {
+        if let Some(outcome) = self.window_check(prepare.epoch, prepare.height, committee) {
+            return Ok(outcome);
+        }
         self.prepare_vote_aggregators
             .entry((prepare.epoch, prepare.height, prepare.phase))
             .or_insert_with(|| Box::new(PrepareMaker::new()))
-            .append(prepare, &self.committee)
+            .append(prepare, committee)
     }
 
+    /// Accumulate coin shares; once threshold is reached the combination is
+    /// shipped off to the crypto worker and the result arrives later on the
+    /// `tx_coin_result` channel passed to `new`, not through this call.
     pub fn add_aba_share_coin(
         &mut self,
         share: RandomnessShare,
+        committee: &Committee,
         pk_set: &PublicKeySet,
-    ) -> ConsensusResult<Option<usize>> {
+    ) -> ConsensusResult<AppendOutcome<()>> {
+        if let Some(outcome) = self.window_check(share.epoch, share.height, committee) {
+            return Ok(outcome);
+        }
         self.share_coin_aggregators
             .entry((share.epoch, share.height, share.round))
             .or_insert_with(|| Box::new(RandomCoinMaker::new()))
-            .append(share, &self.committee, pk_set)
+            .append(share, committee, pk_set, &self.coin_worker)
     }
 
-    pub fn cleanup(&mut self, epoch: SeqNumber, height: SeqNumber) {
-        let size = self.committee.size() as u64;
+    pub fn cleanup(&mut self, epoch: SeqNumber, height: SeqNumber, committee: &Committee) {
+        self.advance_committed(epoch, height, committee);
+        let size = committee.size() as u64;
         let rank = epoch * size + height;
         self.echo_vote_aggregators
             .retain(|(e, h, ..), _| e * size + h > rank);
@@ -87,27 +496,187 @@ impl Aggregator {
             .retain(|(e, h, ..), _| e * size + h > rank);
         self.prepare_vote_aggregators
             .retain(|(e, h, ..), _| e * size + h > rank);
+        // Drops the `RandomCoinMaker` for any pruned slot. A combination job
+        // already in flight on the worker thread runs to completion, but its
+        // result has nowhere to land once the slot is gone: the core drops
+        // `CoinResult`s for instances it has already finished with.
         self.share_coin_aggregators
             .retain(|(e, h, _), _| e * size + h > rank);
     }
+
+    /// Remaining stake needed to close the given in-flight quorum, or 0 if
+    /// there is no such maker (not yet touched, or already closed and
+    /// cleaned up). `committee` must be the committee governing the epoch
+    /// embedded in `key`.
+    pub fn weight_toward(&self, key: VoteKind, committee: &Committee) -> Stake {
+        match key {
+            VoteKind::Echo(e, h) => self
+                .echo_vote_aggregators
+                .get(&(e, h))
+                .map_or(0, |m| m.weight_toward(committee.quorum_threshold())),
+            VoteKind::Ready(e, h) => self
+                .ready_vote_aggregators
+                .get(&(e, h))
+                .map_or(0, |m| m.weight_toward(committee.quorum_threshold())),
+            VoteKind::Prepare(e, h, phase) => self
+                .prepare_vote_aggregators
+                .get(&(e, h, phase))
+                .map_or(0, |m| m.weight_toward(committee.quorum_threshold())),
+            VoteKind::Coin(e, h, r) => self
+                .share_coin_aggregators
+                .get(&(e, h, r))
+                .map_or(0, |m| m.weight_toward(committee.random_coin_threshold())),
+        }
+    }
+
+    /// Progress towards quorum for the given in-flight vote, as a fraction
+    /// in `[0, 1]`. `committee` must be the committee governing the epoch
+    /// embedded in `key`.
+    pub fn fraction_to_quorum(&self, key: VoteKind, committee: &Committee) -> f64 {
+        match key {
+            VoteKind::Echo(e, h) => self
+                .echo_vote_aggregators
+                .get(&(e, h))
+                .map_or(0.0, |m| m.fraction_to_quorum(committee.quorum_threshold())),
+            VoteKind::Ready(e, h) => self
+                .ready_vote_aggregators
+                .get(&(e, h))
+                .map_or(0.0, |m| m.fraction_to_quorum(committee.quorum_threshold())),
+            VoteKind::Prepare(e, h, phase) => self
+                .prepare_vote_aggregators
+                .get(&(e, h, phase))
+                .map_or(0.0, |m| m.fraction_to_quorum(committee.quorum_threshold())),
+            VoteKind::Coin(e, h, r) => self
+                .share_coin_aggregators
+                .get(&(e, h, r))
+                .map_or(0.0, |m| m.fraction_to_quorum(committee.random_coin_threshold())),
+        }
+    }
+
+    /// Authorities that have contributed to the given in-flight vote so far.
+    pub fn contributors(&self, key: VoteKind) -> &[PublicKey] {
+        match key {
+            VoteKind::Echo(e, h) => self
+                .echo_vote_aggregators
+                .get(&(e, h))
+                .map_or(&[], |m| m.contributors()),
+            VoteKind::Ready(e, h) => self
+                .ready_vote_aggregators
+                .get(&(e, h))
+                .map_or(&[], |m| m.contributors()),
+            VoteKind::Prepare(e, h, phase) => self
+                .prepare_vote_aggregators
+                .get(&(e, h, phase))
+                .map_or(&[], |m| m.contributors()),
+            VoteKind::Coin(e, h, r) => self
+                .share_coin_aggregators
+                .get(&(e, h, r))
+                .map_or(&[], |m| m.contributors()),
+        }
+    }
 }
 
+// FIXME(chunk1-3): NOT DONE, NOT MAINTAINER-SIGNED-OFF. `CompactProof` is
+// wire-size compaction only -- a signer bitmap -- not the aggregate
+// threshold-signature scheme ("compact threshold signatures for
+// RBCProof/Prepare quorums") the request asked for. `verify` below is
+// still O(weight): one plain-signature check per signer, same
+// verify_vote_signature loop the old per-author `RBCProof` ran. Getting
+// real O(1) verification needs echo/ready/prepare votes signed with
+// `SignatureShare`s against a `PublicKeySet` instead of today's plain
+// per-author `Signature`s (see `combine_signatures` at the top of this
+// file, already used for coin shares) -- a wholesale move onto the
+// threshold scheme that also requires a matching `messages.rs` signing
+// change not present in this tree. Do not mark this request closed on the
+// strength of this comment; it stays open until either that scheme lands
+// or a maintainer explicitly re-scopes/retitles the ticket to "wire-size
+// compaction" and signs off on that narrower scope in review.
+///
+/// What this struct does deliver: instead of `RBCProof`'s
+/// `Vec<(PublicKey, Signature)>` growing with the committee, a bitmap of
+/// which committee members signed plus their signatures alone, re-keyed to
+/// authors via `committee.name(id)` at verify time instead of storing each
+/// signer's `PublicKey` again. Each signer's `Signature` is still carried,
+/// so a quorum's bytes in `SyncReplyMsg`/persisted proofs shrink by the
+/// per-signer `PublicKey`s, not by the signatures.
+#[derive(Clone, Debug)]
+pub struct CompactProof {
+    pub epoch: SeqNumber,
+    pub height: SeqNumber,
+    pub tag: u8,
+    pub signers: Vec<bool>,
+    pub signatures: Vec<Signature>,
+}
+
+impl CompactProof {
+    /// Number of committee members whose signature this proof carries --
+    /// the direct replacement for the old `RBCProof::votes.len()`.
+    pub fn weight(&self) -> usize {
+        self.signers.iter().filter(|set| **set).count()
+    }
+
+    /// Re-derive each signer's identity from the bitmap and `committee`,
+    /// then verify every retained signature over `value`. Cheaper to carry
+    /// around than the old vote set, but still O(weight) to check -- this is
+    /// a partial delivery of the O(1) aggregate-signature verification
+    /// originally asked for (see the struct doc comment above); closing that
+    /// gap needs echo/ready votes moved onto the threshold scheme, not just
+    /// a smaller proof.
+    pub fn verify(&self, committee: &Committee, value: &Digest) -> ConsensusResult<()> {
+        let domain = if self.tag == RBC_READY {
+            VoteDomain::RbcReady
+        } else {
+            VoteDomain::RbcEcho
+        };
+        let ids: Vec<usize> = self
+            .signers
+            .iter()
+            .enumerate()
+            .filter(|(_, set)| **set)
+            .map(|(id, _)| id)
+            .collect();
+        ensure!(
+            ids.len() == self.signatures.len(),
+            ConsensusError::InvalidThresholdSignature(self.epoch, self.height)
+        );
+        let stake: Stake = ids
+            .iter()
+            .map(|&id| committee.stake(&committee.name(id)))
+            .sum();
+        ensure!(
+            stake >= committee.quorum_threshold(),
+            ConsensusError::InvalidThresholdSignature(self.epoch, self.height)
+        );
+        for (id, signature) in ids.into_iter().zip(self.signatures.iter()) {
+            let author = committee.name(id);
+            verify_vote_signature(&author, signature, domain, self.epoch, self.height, 0, &value.to_vec())?;
+        }
+        Ok(())
+    }
+}
+
+/// Dedupes by author, accumulates stake into a `CompactProof` quorum, and
+/// turns a second differing vote into an equivocation proof instead of a
+/// plain error. A thin instantiation of `VoteCollector<()>`.
 struct RBCProofMaker {
-    weight: Stake,
     votes: Vec<(PublicKey, Signature)>,
-    used: HashSet<PublicKey>,
+    used: HashMap<PublicKey, SignedVote>,
+    collector: VoteCollector<()>,
 }
 
 impl RBCProofMaker {
     pub fn new() -> Self {
         Self {
-            weight: 0,
             votes: Vec::new(),
-            used: HashSet::new(),
+            used: HashMap::new(),
+            collector: VoteCollector::new(),
         }
     }
 
-    /// Try to append a signature to a (partial) quorum.
+    /// Try to append a signature to a (partial) quorum. If `author` already
+    /// voted for this slot, the new vote is compared against the one on
+    /// file: an identical resend is a harmless duplicate, a differing
+    /// payload is equivocation and is reported instead of a plain error.
     pub fn append(
         &mut self,
         epoch: SeqNumber,
@@ -115,127 +684,356 @@ impl RBCProofMaker {
         author: PublicKey,
         tag: u8,
         siganture: Signature,
+        vote: SignedVote,
         committee: &Committee,
-    ) -> ConsensusResult<Option<RBCProof>> {
-        // Ensure it is the first time this authority votes.
-        ensure!(
-            self.used.insert(author),
-            ConsensusError::AuthorityReuseinRBCVote(author)
-        );
+    ) -> ConsensusResult<AppendOutcome<CompactProof>> {
+        let domain = if tag == RBC_READY {
+            VoteDomain::RbcReady
+        } else {
+            VoteDomain::RbcEcho
+        };
+        let digest = match &vote {
+            SignedVote::Echo(v) => v.digest.clone(),
+            SignedVote::Ready(v) => v.digest.clone(),
+            _ => return Err(ConsensusError::InvalidVoteSignature(author)),
+        };
+        verify_vote_signature(
+            &author,
+            &siganture,
+            domain,
+            epoch,
+            height,
+            0,
+            &digest.to_vec(),
+        )?;
+
+        if let Some(first) = self.used.get(&author) {
+            if *first != vote {
+                return Ok(AppendOutcome::Equivocation(Box::new(EquivocationProof {
+                    author,
+                    first: first.clone(),
+                    second: vote,
+                })));
+            }
+            return Ok(AppendOutcome::Pending);
+        }
+        self.used.insert(author, vote);
         self.votes.push((author, siganture));
-        self.weight += committee.stake(&author);
+        self.collector.record(author, committee.stake(&author), |_| {});
 
-        if self.weight == committee.quorum_threshold()
-            || (tag == RBC_READY && self.weight == committee.random_coin_threshold())
+        if self.collector.weight() == committee.quorum_threshold()
+            || (tag == RBC_READY && self.collector.weight() == committee.random_coin_threshold())
         {
-            let proof = RBCProof::new(epoch, height, self.votes.clone(), tag);
-            return Ok(Some(proof));
+            // Cheap double-check: re-verify every accumulated signature
+            // before handing out a proof that will be persisted and
+            // replayed during catch-up.
+            for (signer, sig) in &self.votes {
+                let vote = self.used.get(signer).expect("vote recorded above");
+                let (d, digest) = match vote {
+                    SignedVote::Echo(v) => (VoteDomain::RbcEcho, v.digest.clone()),
+                    SignedVote::Ready(v) => (VoteDomain::RbcReady, v.digest.clone()),
+                    _ => return Err(ConsensusError::InvalidVoteSignature(*signer)),
+                };
+                verify_vote_signature(signer, sig, d, epoch, height, 0, &digest.to_vec())?;
+            }
+            let mut signers = vec![false; committee.size()];
+            let mut signatures = Vec::with_capacity(self.votes.len());
+            for (signer, sig) in &self.votes {
+                signers[committee.id(*signer)] = true;
+                signatures.push(sig.clone());
+            }
+            let proof = CompactProof {
+                epoch,
+                height,
+                tag,
+                signers,
+                signatures,
+            };
+            return Ok(AppendOutcome::Quorum(proof));
         }
-        Ok(None)
+        Ok(AppendOutcome::Pending)
+    }
+
+    fn weight_toward(&self, quorum: Stake) -> Stake {
+        self.collector.weight_toward(quorum)
+    }
+
+    fn fraction_to_quorum(&self, quorum: Stake) -> f64 {
+        self.collector.fraction_to_quorum(quorum)
+    }
+
+    fn contributors(&self) -> &[PublicKey] {
+        self.collector.contributors()
     }
 }
 
+/// Dedupes by author and splits stake between the `OPT`/`PES` tallies of a
+/// prepare quorum. A thin instantiation of `VoteCollector<(Stake, Stake)>`.
 struct PrepareMaker {
-    optnum: Stake,
-    pesnum: Stake,
-    used: HashSet<PublicKey>,
+    used: HashMap<PublicKey, SignedVote>,
+    collector: VoteCollector<(Stake, Stake)>,
 }
 
 impl PrepareMaker {
     pub fn new() -> Self {
         Self {
-            optnum: 0,
-            pesnum: 0,
-            used: HashSet::new(),
+            used: HashMap::new(),
+            collector: VoteCollector::new(),
         }
     }
 
-    /// Try to append a signature to a (partial) quorum.
+    /// Try to append a signature to a (partial) quorum. A second, differing
+    /// `Prepare` from the same author (e.g. signing both `OPT` and `PES` in
+    /// one phase) is reported as equivocation instead of `AuthorityReuse`.
     pub fn append(
         &mut self,
         prepare: Prepare,
         committee: &Committee,
-    ) -> ConsensusResult<Option<(u8, bool)>> {
-        // Ensure it is the first time this authority votes.
+    ) -> ConsensusResult<AppendOutcome<(u8, bool)>> {
         let author = prepare.author;
-        ensure!(
-            self.used.insert(author),
-            ConsensusError::AuthorityReuseinPrepare(author)
-        );
-        if prepare.val == OPT {
-            self.optnum += committee.stake(&author)
+        let domain = if prepare.phase == PRE_ONE {
+            VoteDomain::PrepareOne
         } else {
-            self.pesnum += committee.stake(&author)
+            VoteDomain::PrepareTwo
+        };
+        verify_vote_signature(
+            &author,
+            &prepare.signature,
+            domain,
+            prepare.epoch,
+            prepare.height,
+            prepare.phase,
+            &[prepare.val],
+        )?;
+        if let Some(first) = self.used.get(&author) {
+            let vote = SignedVote::Prepare(prepare);
+            if *first != vote {
+                return Ok(AppendOutcome::Equivocation(Box::new(EquivocationProof {
+                    author,
+                    first: first.clone(),
+                    second: vote,
+                })));
+            }
+            return Ok(AppendOutcome::Pending);
         }
-        let total = self.optnum + self.pesnum;
+        self.used
+            .insert(author, SignedVote::Prepare(prepare.clone()));
+        let stake = committee.stake(&author);
+        let val = prepare.val;
+        self.collector.record(author, stake, |tally| {
+            if val == OPT {
+                tally.0 += stake;
+            } else {
+                tally.1 += stake;
+            }
+        });
+        let (optnum, pesnum) = *self.collector.tally();
 
-        if total == committee.quorum_threshold() {
+        if self.collector.weight() == committee.quorum_threshold() {
             if prepare.phase == PRE_ONE {
-                if self.optnum >= committee.quorum_threshold() {
-                    return Ok(Some((OPT, true)));
-                } else if self.optnum > 0 {
-                    return Ok(Some((OPT, false)));
+                if optnum >= committee.quorum_threshold() {
+                    return Ok(AppendOutcome::Quorum((OPT, true)));
+                } else if optnum > 0 {
+                    return Ok(AppendOutcome::Quorum((OPT, false)));
                 }
-                return Ok(Some((PES, false)));
+                return Ok(AppendOutcome::Quorum((PES, false)));
             } else if prepare.phase == PRE_TWO {
-                if self.pesnum >= committee.quorum_threshold() {
-                    return Ok(Some((PES, true)));
-                } else if self.pesnum > 0 {
-                    return Ok(Some((PES, false)));
+                if pesnum >= committee.quorum_threshold() {
+                    return Ok(AppendOutcome::Quorum((PES, true)));
+                } else if pesnum > 0 {
+                    return Ok(AppendOutcome::Quorum((PES, false)));
                 }
-                return Ok(Some((OPT, false)));
+                return Ok(AppendOutcome::Quorum((OPT, false)));
             }
         }
-        Ok(None)
+        Ok(AppendOutcome::Pending)
+    }
+
+    fn weight_toward(&self, quorum: Stake) -> Stake {
+        self.collector.weight_toward(quorum)
+    }
+
+    fn fraction_to_quorum(&self, quorum: Stake) -> f64 {
+        self.collector.fraction_to_quorum(quorum)
+    }
+
+    fn contributors(&self) -> &[PublicKey] {
+        self.collector.contributors()
     }
 }
 
+/// Dedupes by author into `used`, accumulates stake, and, once threshold is
+/// reached, dispatches the collected coin shares to the crypto worker. A
+/// second, differing share from an already-recorded author is turned into
+/// an equivocation proof instead of a plain `AuthorityReuseinCoin` error,
+/// the same as `RBCProofMaker`/`PrepareMaker`.
 struct RandomCoinMaker {
-    weight: Stake,
     shares: Vec<RandomnessShare>,
-    used: HashSet<PublicKey>,
+    /// Authors whose share has actually been confirmed valid, either by the
+    /// batch verifier or the per-item fallback. Only these are permanently
+    /// spent -- see `pending_authors` for shares still awaiting verification.
+    used: HashMap<PublicKey, SignedVote>,
+    /// Authors with a share currently queued in `batch` but not yet
+    /// verified. Checked alongside `used` for dedup/equivocation so a
+    /// second, differing submission can't race in ahead of the first's
+    /// verification, but -- unlike `used` -- an entry here is removed if
+    /// that share turns out to fail verification, so a genuinely honest
+    /// author whose share was merely corrupted in transit can resubmit
+    /// instead of being locked out for the rest of the round.
+    pending_authors: HashMap<PublicKey, SignedVote>,
+    dispatched: bool,
+    collector: VoteCollector<()>,
+    batch: MultiThreadedBatchVerifier,
+    /// Shares queued into `batch` but not yet verified -- and so not yet
+    /// folded into `collector`/`shares` -- along with the stake they'd add
+    /// if they turn out valid.
+    pending: Vec<RandomnessShare>,
+    pending_stake: Stake,
 }
 
 impl RandomCoinMaker {
     pub fn new() -> Self {
         Self {
-            weight: 0,
             shares: Vec::new(),
-            used: HashSet::new(),
+            used: HashMap::new(),
+            pending_authors: HashMap::new(),
+            dispatched: false,
+            collector: VoteCollector::new(),
+            batch: MultiThreadedBatchVerifier::new(),
+            pending: Vec::new(),
+            pending_stake: 0,
         }
     }
 
-    /// Try to append a signature to a (partial) quorum.
+    /// Queue a share's signature for later, batched verification instead of
+    /// checking it inline: under a flood of `N-f` shares arriving for the
+    /// same round, that's one pairing check per message on the core's
+    /// single event-loop thread. Once enough shares (by stake, not just
+    /// count) have queued to plausibly close the quorum, verifies the whole
+    /// pending batch in parallel and only then folds the valid ones into
+    /// `collector`/`shares`, dispatching the combination job to `worker` the
+    /// same as before. Guarded by `dispatched` so a slot only ever ships one
+    /// combination job.
     pub fn append(
         &mut self,
         share: RandomnessShare,
         committee: &Committee,
         pk_set: &PublicKeySet,
-    ) -> ConsensusResult<Option<usize>> {
+        worker: &CoinWorker,
+    ) -> ConsensusResult<AppendOutcome<()>> {
         let author = share.author;
-        // Ensure it is the first time this authority votes.
-        ensure!(
-            self.used.insert(author),
-            ConsensusError::AuthorityReuseinCoin(author)
-        );
-        self.shares.push(share.clone());
-        self.weight += committee.stake(&author);
-        if self.weight == committee.random_coin_threshold() {
-            // self.weight = 0; // Ensures QC is only made once.
-            let mut sigs = BTreeMap::new();
-            // Check the random shares.
-            for share in self.shares.clone() {
-                sigs.insert(
-                    committee.id(share.author.clone()),
-                    share.signature_share.clone(),
-                );
+        // If `author` already contributed a share for this slot, a resend
+        // of the identical share is a harmless duplicate; anything else is
+        // reported as equivocation instead of a plain error. Checked before
+        // queuing so this is rejected for free, without waiting for the
+        // batch to drain.
+        let vote = SignedVote::Coin(share.clone());
+        if let Some(first) = self
+            .used
+            .get(&author)
+            .or_else(|| self.pending_authors.get(&author))
+        {
+            if *first != vote {
+                return Ok(AppendOutcome::Equivocation(Box::new(EquivocationProof {
+                    author,
+                    first: first.clone(),
+                    second: vote,
+                })));
             }
-            if let Ok(sig) = pk_set.combine_signatures(sigs.iter()) {
-                let id = usize::from_be_bytes((&sig.to_bytes()[0..8]).try_into().unwrap()) % 2;
+            return Ok(AppendOutcome::Pending);
+        }
+        // Not yet confirmed valid: reserve the slot against a second,
+        // differing submission racing in while this one is still in
+        // `batch`, but don't treat it as spent (`used`) until verification
+        // actually confirms it -- see `pending_authors`.
+        self.pending_authors.insert(author, vote);
+        let (epoch, height, round) = (share.epoch, share.height, share.round);
+        let digest = vote_digest(VoteDomain::CoinShare, epoch, height, 0, &round.to_le_bytes());
+        let index = committee.id(author);
+        self.batch
+            .queue(author, index, digest, share.signature_share.clone());
+        self.pending_stake += committee.stake(&author);
+        self.pending.push(share);
+
+        if self.dispatched
+            || self.collector.weight() + self.pending_stake < committee.random_coin_threshold()
+        {
+            return Ok(AppendOutcome::Pending);
+        }
 
-                return Ok(Some(id));
+        let pending = std::mem::take(&mut self.pending);
+        self.pending_stake = 0;
+        let mut first_fault = None;
+        if self.batch.drain_and_verify(pk_set).is_err() {
+            // The batch wasn't clean: fall back to per-item verification to
+            // isolate exactly which share(s) are bad instead of discarding
+            // the whole batch, including the honest shares it also
+            // contains.
+            for share in pending {
+                let digest = vote_digest(
+                    VoteDomain::CoinShare,
+                    share.epoch,
+                    share.height,
+                    0,
+                    &share.round.to_le_bytes(),
+                );
+                let index = committee.id(share.author);
+                if pk_set
+                    .public_key_share(index)
+                    .verify(&share.signature_share, digest.to_vec())
+                {
+                    if let Some(vote) = self.pending_authors.remove(&share.author) {
+                        self.used.insert(share.author, vote);
+                    }
+                    let stake = committee.stake(&share.author);
+                    self.collector.record(share.author, stake, |_| {});
+                    self.shares.push(share);
+                } else {
+                    // Verification failed: free the slot instead of leaving
+                    // it permanently reserved, so a retransmission of a
+                    // merely-corrupted share can still be accepted.
+                    self.pending_authors.remove(&share.author);
+                    first_fault.get_or_insert(share.author);
+                }
+            }
+        } else {
+            for share in pending {
+                if let Some(vote) = self.pending_authors.remove(&share.author) {
+                    self.used.insert(share.author, vote);
+                }
+                let stake = committee.stake(&share.author);
+                self.collector.record(share.author, stake, |_| {});
+                self.shares.push(share);
             }
         }
-        Ok(None)
+
+        if self.collector.weight() >= committee.random_coin_threshold() && !self.dispatched {
+            self.dispatched = true;
+            worker.dispatch(CoinJob {
+                epoch,
+                height,
+                round,
+                shares: self.shares.clone(),
+                pk_set: pk_set.clone(),
+                committee: committee.clone(),
+            });
+        }
+
+        match first_fault {
+            Some(author) => Err(ConsensusError::InvalidVoteSignature(author)),
+            None => Ok(AppendOutcome::Pending),
+        }
+    }
+
+    fn weight_toward(&self, quorum: Stake) -> Stake {
+        self.collector.weight_toward(quorum)
+    }
+
+    fn fraction_to_quorum(&self, quorum: Stake) -> f64 {
+        self.collector.fraction_to_quorum(quorum)
+    }
+
+    fn contributors(&self) -> &[PublicKey] {
+        self.collector.contributors()
     }
 }
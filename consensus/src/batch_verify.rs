@@ -0,0 +1,87 @@
+use crypto::{Digest, PublicKey};
+use rayon::prelude::*;
+use threshold_crypto::{PublicKeySet, SignatureShare};
+
+/// One deferred coin-share signature check: `author`'s committee index (how
+/// `PublicKeySet` keys its public-key shares), the digest the share is
+/// supposed to cover, and the raw BLS signature share itself.
+struct PendingVerification {
+    author: PublicKey,
+    index: usize,
+    digest: Digest,
+    signature_share: SignatureShare,
+}
+
+// FIXME(chunk2-3): NOT DONE, NOT MAINTAINER-SIGNED-OFF. This is a partial
+// delivery of "batch-verify coin shares" -- `rayon`-parallelized
+// per-item pairing checks, not the randomized-linear-combination batch
+// verification the request specified (see the complexity note below for
+// exactly what's missing and why). Do not mark this request closed on
+// the strength of this comment; it stays open until either the real
+// batching lands or a maintainer explicitly re-scopes/retitles the
+// ticket to "parallelized verification" and signs off on that narrower
+// scope in review.
+///
+/// Partial delivery of "batch-verify coin shares": accumulates
+/// verifications for one `(epoch, height, round)` instance so a quorum's
+/// worth can be checked together across a `rayon` thread pool instead of
+/// one pairing check per message on the core's single event-loop thread.
+///
+/// This is parallelization, not the algorithmic batching the request
+/// asked for. A true randomized-linear-combination batch check (scale each
+/// item by a fresh random scalar, sum, and verify one aggregate pairing
+/// equation) needs pairing primitives `threshold_crypto`'s public API
+/// doesn't expose outside the crate, so the CPU-bound cost of a single
+/// pairing check is unchanged -- what's here still runs the same N
+/// individual `PublicKeyShare::verify` pairing checks, just spread across
+/// however many cores `rayon` has, turning the O(N) sequential wall-clock
+/// of an `N-f`-share flood into roughly O(N / cores). A real wall-clock
+/// win under multi-core, but not the single-core complexity reduction
+/// "batch verification" implies.
+#[derive(Default)]
+pub struct MultiThreadedBatchVerifier {
+    pending: Vec<PendingVerification>,
+}
+
+impl MultiThreadedBatchVerifier {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue a share's signature for later verification instead of
+    /// checking it inline.
+    pub fn queue(
+        &mut self,
+        author: PublicKey,
+        index: usize,
+        digest: Digest,
+        signature_share: SignatureShare,
+    ) {
+        self.pending.push(PendingVerification {
+            author,
+            index,
+            digest,
+            signature_share,
+        });
+    }
+
+    /// Verify every queued share in parallel and drain the queue.
+    ///
+    /// On success, `Ok(())`. On failure, `Err(author)` of one bad share --
+    /// not necessarily the first queued, since checks run concurrently. The
+    /// caller is expected to fall back to sequential per-item verification
+    /// over the same shares if it needs to isolate every bad contributor,
+    /// not just one.
+    pub fn drain_and_verify(&mut self, pk_set: &PublicKeySet) -> Result<(), PublicKey> {
+        let pending = std::mem::take(&mut self.pending);
+        let failure = pending.par_iter().find_any(|item| {
+            !pk_set
+                .public_key_share(item.index)
+                .verify(&item.signature_share, item.digest.to_vec())
+        });
+        match failure {
+            Some(item) => Err(item.author),
+            None => Ok(()),
+        }
+    }
+}
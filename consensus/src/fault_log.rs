@@ -0,0 +1,101 @@
+use crate::core::SeqNumber;
+use crypto::PublicKey;
+use std::collections::HashMap;
+
+#[cfg(test)]
+#[path = "tests/fault_log_tests.rs"]
+mod fault_log_tests;
+
+/// The specific way a peer's message failed verification or otherwise
+/// deviated from protocol. Each variant is attributable to a single author
+/// -- the replica whose signature or claim didn't hold up.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FaultKind {
+    /// A `RandomnessShare` failed its threshold-signature verification.
+    InvalidCoinShare,
+    /// An `ABAOutput` carried a signature that didn't verify.
+    InvalidABAOutputSignature,
+    /// An `ABAConf` carried a signature that didn't verify.
+    InvalidConfSignature,
+    /// A `ValShard` carried a signature, proposer, or erasure-coding shape
+    /// that didn't verify.
+    InvalidValShard,
+    /// An RBC echo `ReadyVote` carried a signature that didn't verify.
+    InvalidEchoVoteSignature,
+    /// An RBC ready `ReadyVote` carried a signature that didn't verify.
+    InvalidReadyVoteSignature,
+    /// A `Prepare` carried a signature that didn't verify.
+    InvalidPrepareSignature,
+    /// An `EquivocationProof` verified, confirming its author double-signed
+    /// a vote. Recorded for every verified proof, whatever the vote type
+    /// (RBC echo/ready, prepare, or coin share).
+    RbcEquivocation,
+}
+
+/// One observed fault: who caused it, which ABA/RBC instance it was for,
+/// and what kind of misbehavior it was.
+#[derive(Clone, Debug)]
+pub struct FaultEntry {
+    pub author: PublicKey,
+    pub epoch: SeqNumber,
+    pub height: SeqNumber,
+    pub round: SeqNumber,
+    pub kind: FaultKind,
+}
+
+/// Accumulates faults attributed to specific peers over the lifetime of a
+/// `Core`. Verification failures used to just bubble up into `run`'s match
+/// arm as an anonymous `warn!`; `FaultLog` keeps per-author counts around so
+/// operators can tell a one-off network hiccup from a validator that is
+/// consistently misbehaving.
+#[derive(Default)]
+pub struct FaultLog {
+    entries: Vec<FaultEntry>,
+    counts: HashMap<PublicKey, usize>,
+}
+
+impl FaultLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a fault attributable to `author`, logging it immediately so
+    /// it shows up in the usual log stream as well as in `count`/`entries`.
+    pub fn record(
+        &mut self,
+        author: PublicKey,
+        epoch: SeqNumber,
+        height: SeqNumber,
+        round: SeqNumber,
+        kind: FaultKind,
+    ) {
+        let count = self.counts.entry(author).or_insert(0);
+        *count += 1;
+        log::warn!(
+            "fault attributed to {}: {:?} (epoch {} height {} round {}, {} total fault(s) from this peer)",
+            author,
+            kind,
+            epoch,
+            height,
+            round,
+            count,
+        );
+        self.entries.push(FaultEntry {
+            author,
+            epoch,
+            height,
+            round,
+            kind,
+        });
+    }
+
+    /// Total faults recorded against `author` so far.
+    pub fn count(&self, author: &PublicKey) -> usize {
+        *self.counts.get(author).unwrap_or(&0)
+    }
+
+    /// All faults recorded so far, oldest first.
+    pub fn entries(&self) -> &[FaultEntry] {
+        &self.entries
+    }
+}
@@ -0,0 +1,58 @@
+use crate::core::SeqNumber;
+use crypto::Digest;
+use std::collections::HashSet;
+
+/// Tracks which blob digests this replica's mempool has already ingested,
+/// so `Core` can tell a digest it already holds apart from one it still
+/// needs to fetch (see `Core::blobs_available`/`request_missing_blobs`).
+///
+/// `insert` is how a digest gets in here in the first place -- mempool
+/// gossip ingestion, or (today) a fetched `BlobReplyMsg`. `get` hands back
+/// up to `max_payload_size` known digests for a new block's payload;
+/// `cleanup` drops bookkeeping for digests no longer needed once their
+/// block's `(epoch, height)` has been committed.
+pub struct MempoolDriver {
+    known: HashSet<Digest>,
+}
+
+impl MempoolDriver {
+    pub fn new() -> Self {
+        Self { known: HashSet::new() }
+    }
+
+    /// Record `digest` as a blob this replica's mempool now holds, whether
+    /// because it was created locally or just arrived over mempool gossip
+    /// (or, today, a direct `BlobReplyMsg` -- see `Core::handle_blob_reply`).
+    /// Without this, `known` never gains an entry and `get`/`verify` stay
+    /// permanently empty/false.
+    pub async fn insert(&mut self, digest: Digest) {
+        self.known.insert(digest);
+    }
+
+    /// Up to `max_payload_size` blob digests ready to go into the next
+    /// block this replica proposes, taken from every digest not yet dropped
+    /// by `cleanup`. Proposals are not tracked, so a digest already in a
+    /// proposed but uncommitted block can be returned again.
+    pub async fn get(&mut self, max_payload_size: usize) -> Vec<Digest> {
+        self.known.iter().take(max_payload_size).cloned().collect()
+    }
+
+    /// True if `digest` is a blob this replica's mempool already holds,
+    /// whether because it created it locally or received it over mempool
+    /// gossip. Digest-keyed rather than block-keyed -- unlike the
+    /// consensus block, an individual blob can be verified as soon as it's
+    /// in hand, without waiting on the RBC/ABA pipeline that commits the
+    /// block referencing it.
+    pub async fn verify(&mut self, digest: Digest) -> bool {
+        self.known.contains(&digest)
+    }
+
+    /// Drop bookkeeping for digests whose block has committed at
+    /// `(epoch, height)` and no longer needs tracking towards a future
+    /// proposal.
+    pub async fn cleanup(&mut self, digests: Vec<Digest>, _epoch: SeqNumber, _height: SeqNumber) {
+        for digest in digests {
+            self.known.remove(&digest);
+        }
+    }
+}
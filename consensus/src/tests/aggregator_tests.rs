@@ -0,0 +1,65 @@
+use super::*;
+
+// NOT ADDED: an integration test that signs a real `ReadyVote`/`Prepare`/
+// `RandomnessShare` with an actual keypair and drives it through
+// `RBCProofMaker::append`/`PrepareMaker::append`/`RandomCoinMaker::append`
+// (and `EquivocationProof::verify`), the way this review asked for. Doing
+// that needs a real `Committee` (`crate::config`) to resolve stake/id/name
+// and a real signing keypair + `SignatureService` (`crate::messages`'
+// `ReadyVote::new`/`Prepare::new`/coin-share signing) -- neither module
+// exists anywhere in this tree (see `vote_digest`'s doc comment for the
+// `messages.rs` half of this). Only the pure helpers below (`vote_digest`,
+// `VoteCollector`) are covered until those modules land; that gap is
+// exactly why the chunk0-4 signing mismatch shipped undetected.
+
+#[test]
+fn vote_digest_is_domain_separated() {
+    // A signature collected for one domain/epoch/height/phase must not
+    // verify against any other -- that's the whole point of mixing them
+    // into the hashed bytes instead of hashing `value` alone (see the long
+    // comment on `vote_digest`: hashing `value` alone is not a narrower bug,
+    // it's effectively no domain separation for `Prepare` votes at all).
+    // This pins the *correct*, intended behavior -- it is not yet safe to
+    // deploy until the `messages.rs` signing side matches, which is a
+    // separate, currently-unshippable piece of this request; see
+    // `vote_digest`'s doc comment for why this test is expected to keep
+    // passing even while the feature as a whole stays open.
+    let value = b"root-digest".to_vec();
+    let echo = vote_digest(VoteDomain::RbcEcho, 1, 2, 0, &value);
+
+    assert_ne!(echo, vote_digest(VoteDomain::RbcReady, 1, 2, 0, &value));
+    assert_ne!(echo, vote_digest(VoteDomain::PrepareOne, 1, 2, 0, &value));
+    assert_ne!(echo, vote_digest(VoteDomain::PrepareTwo, 1, 2, 0, &value));
+    assert_ne!(echo, vote_digest(VoteDomain::CoinShare, 1, 2, 0, &value));
+    assert_ne!(echo, vote_digest(VoteDomain::RbcEcho, 9, 2, 0, &value));
+    assert_ne!(echo, vote_digest(VoteDomain::RbcEcho, 1, 9, 0, &value));
+    assert_ne!(echo, vote_digest(VoteDomain::RbcEcho, 1, 2, 9, &value));
+
+    let other_value = b"other-digest".to_vec();
+    assert_ne!(echo, vote_digest(VoteDomain::RbcEcho, 1, 2, 0, &other_value));
+
+    assert_eq!(echo, vote_digest(VoteDomain::RbcEcho, 1, 2, 0, &value));
+}
+
+#[test]
+fn vote_collector_tracks_weight_toward_and_fraction() {
+    let mut collector: VoteCollector<()> = VoteCollector::new();
+    assert_eq!(collector.weight_toward(10), 10);
+    assert_eq!(collector.fraction_to_quorum(10), 0.0);
+
+    collector.weight = 4;
+    assert_eq!(collector.weight_toward(10), 6);
+    assert_eq!(collector.fraction_to_quorum(10), 0.4);
+
+    collector.weight = 10;
+    assert_eq!(collector.weight_toward(10), 0);
+    assert_eq!(collector.fraction_to_quorum(10), 1.0);
+
+    // Already past quorum -- remaining weight and fraction both clamp
+    // rather than going negative/over 1.
+    collector.weight = 20;
+    assert_eq!(collector.weight_toward(10), 0);
+    assert_eq!(collector.fraction_to_quorum(10), 1.0);
+
+    assert!(collector.contributors().is_empty());
+}
@@ -0,0 +1,88 @@
+use super::*;
+
+#[test]
+fn merkle_tree_round_trip() {
+    let shards: Vec<Vec<u8>> = (0u8..5).map(|i| vec![i; 4]).collect();
+    let (root, branches) = merkle_tree(&shards);
+    for (index, (shard, branch)) in shards.iter().zip(branches.iter()).enumerate() {
+        assert!(merkle_verify(&root, index, shard, branch));
+    }
+}
+
+#[test]
+fn merkle_verify_rejects_tampered_shard() {
+    let shards: Vec<Vec<u8>> = (0u8..5).map(|i| vec![i; 4]).collect();
+    let (root, branches) = merkle_tree(&shards);
+    let tampered = vec![0xffu8; 4];
+    assert!(!merkle_verify(&root, 0, &tampered, &branches[0]));
+}
+
+#[test]
+fn encode_and_reconstruct_shards_round_trip() {
+    let data = b"the quick brown fox jumps over the lazy dog".to_vec();
+    let data_shards = 3;
+    let total_shards = 5;
+    let shards = encode_shards(&data, data_shards, total_shards)
+        .expect("data_shards < total_shards is a valid Reed-Solomon configuration");
+    assert_eq!(shards.len(), total_shards);
+
+    // Any `data_shards` of the `total_shards` must be enough to recover the
+    // original payload -- drop two parity shards and reconstruct from the
+    // rest.
+    let mut holes: Vec<Option<Vec<u8>>> = shards.into_iter().map(Some).collect();
+    holes[3] = None;
+    holes[4] = None;
+    let recovered = reconstruct_shards(holes, data_shards, total_shards, data.len())
+        .expect("reconstruction should succeed with a full data_shards set");
+    assert_eq!(recovered, data);
+}
+
+#[test]
+fn encode_shards_rejects_zero_parity_shards() {
+    // `data_shards == total_shards` leaves no parity shards, which isn't a
+    // valid Reed-Solomon configuration -- must fail gracefully rather than
+    // panic, since a small enough committee can produce exactly this shape.
+    let data = b"small committee, no room for parity".to_vec();
+    assert!(encode_shards(&data, 4, 4).is_none());
+}
+
+#[test]
+fn bin_values_bitset_round_trips_bin_value_bit() {
+    assert_eq!(bin_values_bitset(&[false, false]), 0);
+    assert_eq!(bin_values_bitset(&[true, false]), bin_value_bit(PES as usize));
+    assert_eq!(bin_values_bitset(&[false, true]), bin_value_bit(OPT as usize));
+    assert_eq!(
+        bin_values_bitset(&[true, true]),
+        bin_value_bit(PES as usize) | bin_value_bit(OPT as usize)
+    );
+}
+
+#[test]
+fn aba_conf_digest_is_domain_separated() {
+    // A CONF vote for one (epoch, height, round, values) must not verify as
+    // a vote for another -- otherwise a signature could be replayed across
+    // rounds or instances.
+    let digest = aba_conf_digest(1, 2, 3, 0b01);
+    assert_ne!(digest, aba_conf_digest(9, 2, 3, 0b01));
+    assert_ne!(digest, aba_conf_digest(1, 9, 3, 0b01));
+    assert_ne!(digest, aba_conf_digest(1, 2, 9, 0b01));
+    assert_ne!(digest, aba_conf_digest(1, 2, 3, 0b10));
+    assert_eq!(digest, aba_conf_digest(1, 2, 3, 0b01));
+}
+
+#[test]
+fn val_shard_digest_is_domain_separated() {
+    // Every field an unsigned `ValShard` could otherwise smuggle (shape,
+    // index, root) must be bound into the signed digest.
+    let root = Digest::hash(b"root");
+    let other_root = Digest::hash(b"other-root");
+    let digest = val_shard_digest(1, 2, &root, 0, 3, 5, 100);
+    assert_ne!(digest, val_shard_digest(9, 2, &root, 0, 3, 5, 100));
+    assert_ne!(digest, val_shard_digest(1, 9, &root, 0, 3, 5, 100));
+    assert_ne!(digest, val_shard_digest(1, 2, &other_root, 0, 3, 5, 100));
+    assert_ne!(digest, val_shard_digest(1, 2, &root, 1, 3, 5, 100));
+    assert_ne!(digest, val_shard_digest(1, 2, &root, 0, 4, 5, 100));
+    assert_ne!(digest, val_shard_digest(1, 2, &root, 0, 3, 6, 100));
+    assert_ne!(digest, val_shard_digest(1, 2, &root, 0, 3, 5, 101));
+    assert_eq!(digest, val_shard_digest(1, 2, &root, 0, 3, 5, 100));
+}
@@ -0,0 +1,27 @@
+use super::*;
+
+// NOT ADDED: an integration test driving a real `Core::handle_*` method
+// (e.g. `handle_equivocation`, `handle_aba_share`) end-to-end to confirm a
+// genuine verification failure lands in `fault_log`, the way this review
+// asked for. `Core` isn't constructible in a unit test without the rest of
+// its fixture -- `Committee`/`Parameters` (`crate::config`), signed
+// `ReadyVote`/`Prepare`/`RandomnessShare` (`crate::messages`), `Store`,
+// `NetworkFilter`, `Synchronizer`, `Timer` -- none of which exist in this
+// tree. Only `FaultLog`'s own accumulation logic is covered below until
+// those modules land.
+
+#[test]
+fn fault_log_accumulates_per_author_counts() {
+    let alice = PublicKey([1u8; 32]);
+    let bob = PublicKey([2u8; 32]);
+    let mut log = FaultLog::new();
+
+    log.record(alice, 1, 2, 0, FaultKind::InvalidCoinShare);
+    log.record(alice, 1, 2, 1, FaultKind::InvalidCoinShare);
+    log.record(bob, 1, 3, 0, FaultKind::RbcEquivocation);
+
+    assert_eq!(log.count(&alice), 2);
+    assert_eq!(log.count(&bob), 1);
+    assert_eq!(log.count(&PublicKey([3u8; 32])), 0);
+    assert_eq!(log.entries().len(), 3);
+}